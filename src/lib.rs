@@ -7,16 +7,23 @@ extern crate log;
 
 use std::sync::{Mutex,Arc,mpsc};
 use std::thread;
+use std::time;
 
 #[macro_use]
 mod process;
+mod remote;
 
 use process::Process;
 use process::Runnable;
+use process::Runtime;
+pub use process::NativeRuntime;
+pub use remote::{serve, Node};
 
 #[derive(Debug)]
 enum Command {
     Spawn(Process),
+    SpawnRemote { node: Node, process: Process },
+    Stop(Process, time::Duration),
     Quit,
 }
 
@@ -32,7 +39,7 @@ type ProcessList = Vec<Process>;
 type SharedProcessList = Arc<Mutex<ProcessList>>;
 
 impl Overlord {
-    pub fn new() -> Overlord {
+    pub fn new<R: Runtime>(runtime: R) -> Overlord {
         let (tx, rx) : (_, mpsc::Receiver<Command>)= mpsc::channel();
 
         let processes: SharedProcessList = Arc::new(Mutex::new(Vec::new()));
@@ -48,7 +55,24 @@ impl Overlord {
                             trace!("Pushed {:?}", p);
                             let mut plist = processes.lock().unwrap();
                             plist.push(p.clone());
-                            p.launch();
+                            runtime.spawn_supervised(p);
+                        }
+                        Command::SpawnRemote { node, process } => {
+                            trace!("Spawning {:?} on {}", process, node);
+                            let mut plist = processes.lock().unwrap();
+                            plist.push(process.clone());
+                            if let Err(e) = remote::spawn_remote(&node, process) {
+                                error!("Failed to spawn on {}: {}", node, e);
+                            }
+                        }
+                        Command::Stop(p, timeout) => {
+                            trace!("Stopping {:?}", p);
+                            // Run the bounded wait off the dispatcher so it can
+                            // keep handling commands during the grace window.
+                            thread::Builder::new()
+                                .name("overlord-stop".to_string())
+                                .spawn(move || p.stop(timeout))
+                                .expect("Failed to spawn stop thread");
                         }
                         Command::Quit => {
                             trace!("Terminating");
@@ -74,6 +98,14 @@ impl Overlord {
     pub fn spawn(&self, process: Process) {
         self.channel.send(Command::Spawn(process)).unwrap();
     }
+
+    pub fn stop(&self, process: Process, timeout: time::Duration) {
+        self.channel.send(Command::Stop(process, timeout)).unwrap();
+    }
+
+    pub fn spawn_remote(&self, node: Node, process: Process) {
+        self.channel.send(Command::SpawnRemote { node, process }).unwrap();
+    }
 }
 
 #[cfg(test)]
@@ -92,7 +124,7 @@ mod tests {
     #[test]
     fn test_run_ls_max_retries() {
         let _ = pretty_env_logger::init();
-        let instance = Overlord::new();
+        let instance = Overlord::new(NativeRuntime);
 
         {
             let processes = instance.processes.lock().unwrap();
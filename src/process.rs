@@ -1,8 +1,12 @@
+extern crate libc;
+
 use std::process;
 use std::time;
 use std::sync::{Mutex,Arc,mpsc};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
 use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::os::unix::process::CommandExt;
 
 #[derive(Debug, PartialEq)]
 pub enum State {
@@ -10,6 +14,7 @@ pub enum State {
     Starting,
     Running,
     Restarting,
+    Stopping,
     Failed,
 }
 
@@ -27,18 +32,24 @@ pub struct _Process {
     pub max_restart_count: u64,
     pub pid: Option<u32>,
 
+    // Run the child in its own session/process group and tear the whole group
+    // down on stop/restart rather than just the direct child.
+    pub kill_group: bool,
+    pub pgid: Option<u32>,
+
+    // Set by `stop` to tell the supervisor loop to stop restarting the child.
+    stopping: Arc<AtomicBool>,
+
     // stdio MPSC channels
     pub stdin: mpsc::Sender<String>,
     pub stdout: mpsc::Receiver<String>,
     pub stderr: mpsc::Receiver<String>,
-    stdin_receiver: mpsc::Receiver<String>,
-    stdout_sender: mpsc::Sender<String>,
-    stderr_sender: mpsc::Sender<String>,
-
-    // stdio BufWriters and BufReaders
-    stdin_writer: Option<BufWriter<process::ChildStdin>>,
-    stdout_reader: Option<BufReader<process::ChildStdout>>,
-    stderr_reader: Option<BufReader<process::ChildStderr>>,
+    // Taken once by the supervisor (`supervise` or `supervise_async`) when a
+    // runtime starts the process, which hands it to the dedicated stdin pump.
+    // Also taken by the remote tunnel when the process runs on another node.
+    pub(crate) stdin_receiver: Option<mpsc::Receiver<String>>,
+    pub(crate) stdout_sender: mpsc::Sender<String>,
+    pub(crate) stderr_sender: mpsc::Sender<String>,
 
     child: Option<Arc<Mutex<process::Child>>>,
 }
@@ -47,33 +58,555 @@ pub type Process = Arc<Mutex<_Process>>;
 
 pub trait Runnable {
     fn define_process(name: &str, path: &str, args: Vec<String>,
-       restart_delay: Option<u64>, cwd: Option<String>) -> Self;
+       restart_delay: Option<u64>, cwd: Option<String>, kill_group: bool) -> Self;
     fn launch(self);
+    fn stop(&self, timeout: time::Duration);
+}
+
+/// Send `signal` to `pid` using libc, logging any failure.
+fn signal(pid: u32, signal: libc::c_int) {
+    // SAFETY: kill(2) is safe to call with an arbitrary pid; at worst it fails
+    // with ESRCH if the process has already exited, which we simply ignore.
+    if unsafe { libc::kill(pid as libc::pid_t, signal) } != 0 {
+        trace!("kill({}, {}) failed", pid, signal);
+    }
+}
+
+/// Spawn a reader thread that forwards complete lines from `stream` into
+/// `sender` as they arrive, named `name` for debugging.
+///
+/// The thread owns the stream for the lifetime of the child; when the child
+/// exits its pipe closes, the `Lines` iterator yields `None`, and the thread
+/// exits. It also stops early if the consumer has dropped its `Receiver`.
+fn spawn_reader<R>(name: &str, stream: R, sender: mpsc::Sender<String>)
+    where R: std::io::Read + Send + 'static {
+    thread::Builder::new()
+        .name(name.to_string())
+        .spawn(move || {
+            for line in BufReader::new(stream).lines() {
+                match line {
+                    Ok(line) => if sender.send(line).is_err() {
+                        // Nobody is listening anymore; stop reading.
+                        break;
+                    },
+                    Err(e) => {
+                        trace!("error reading {}: {}", name, e);
+                        break;
+                    }
+                }
+            }
+        }).expect("Failed to spawn reader thread");
+}
+
+/// Send `signal` to the whole process group led by `pgid`.
+///
+/// Passing the negated pgid to kill(2) delivers the signal to every member of
+/// the group, so descendants forked by the supervised program are torn down
+/// too. This is the command-group teardown technique used by watchexec.
+fn signal_group(pgid: u32, signal: libc::c_int) {
+    // SAFETY: see `signal`; the negative pid selects the process group.
+    if unsafe { libc::kill(-(pgid as libc::pid_t), signal) } != 0 {
+        trace!("kill(-{}, {}) failed", pgid, signal);
+    }
+}
+
+/// Abstracts how a supervised process is driven.
+///
+/// This is the sole dispatch point between supervision strategies: the default
+/// [`NativeRuntime`] dedicates one OS thread per process and polls `try_wait`,
+/// while an async backend can reap many children from a single background
+/// thread. It mirrors the libgreen/libnative split, where one `Runtime` trait
+/// is all that stands between 1:1 and M:N scheduling.
+pub trait Runtime: Copy + Send + Sync + 'static {
+    /// Drive `process` through its supervise/restart lifecycle. Returns once
+    /// supervision has been set up; the process keeps running in the background.
+    fn spawn_supervised(&self, process: Process);
+
+    /// Block until `child` exits and return its status.
+    fn wait(&self, child: &Arc<Mutex<process::Child>>)
+        -> std::io::Result<process::ExitStatus>;
+
+    /// Sleep for `dur`.
+    fn sleep(&self, dur: time::Duration);
+}
+
+/// The default runtime: one dedicated OS thread per process, waiting on the
+/// child by polling `try_wait` every 10ms.
+#[derive(Debug, Clone, Copy)]
+pub struct NativeRuntime;
+
+impl Runtime for NativeRuntime {
+    fn spawn_supervised(&self, process: Process) {
+        let rt = *self;
+        let driver = process.clone();
+        let handle = thread::Builder::new()
+            .name("overlord".to_string())
+            .spawn(move || supervise(driver, rt))
+            .expect("Failed to spawn process");
+        process.lock().unwrap().handle = Some(handle);
+    }
+
+    fn wait(&self, child: &Arc<Mutex<process::Child>>)
+        -> std::io::Result<process::ExitStatus> {
+        // Poll rather than `child.wait()`: we only hold the child's Mutex for
+        // the duration of each `try_wait`, so `stop` can still kill it from
+        // another thread between polls.
+        loop {
+            if let Some(status) = child.lock().unwrap().try_wait()? {
+                return Ok(status);
+            }
+            self.sleep(time::Duration::from_millis(10));
+        }
+    }
+
+    fn sleep(&self, dur: time::Duration) {
+        thread::sleep(dur);
+    }
+}
+
+/// Supervise `process` on the current thread, (re)spawning the child and
+/// driving waits/sleeps through `rt` until the process is stopped or fails.
+fn supervise<R: Runtime>(lockable: Process, rt: R) {
+    // A single long-lived writer thread drains the stdin channel and writes
+    // into whichever child is currently running. The writer is swapped on
+    // every (re)spawn through this shared slot.
+    let stdin_writer: Arc<Mutex<Option<BufWriter<process::ChildStdin>>>> =
+        Arc::new(Mutex::new(None));
+    {
+        let stdin_receiver = lockable.lock().unwrap().stdin_receiver.take()
+            .expect("process supervised more than once");
+        let stdin_writer = stdin_writer.clone();
+        let stopping = lockable.lock().unwrap().stopping.clone();
+        thread::Builder::new()
+            .name("overlord-stdin".to_string())
+            .spawn(move || {
+                for input in stdin_receiver.iter() {
+                    // The _Process keeps a stdin Sender for its whole life, so
+                    // the channel never closes. The flag is checked as each
+                    // line arrives: the pump exits on the first line received
+                    // once the process is stopping, and stays blocked until then.
+                    if stopping.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    info!("received: {}", input);
+                    if let Some(ref mut writer) = *stdin_writer.lock().unwrap() {
+                        // The child's stdin may have closed between (re)spawns or
+                        // after a stop; drop the line rather than panicking on a
+                        // broken pipe so the pump survives for the next child.
+                        if let Err(e) = writer.write_all(input.as_bytes())
+                            .and_then(|_| writer.flush()) {
+                            trace!("dropping stdin line; pipe closed: {}", e);
+                        }
+                    }
+                }
+            }).expect("Failed to spawn stdin pump");
+    }
+
+    loop {
+        // Check the shutdown flag before (re)spawning. `stop` may have been
+        // called during the restart-delay window, when there was no live child
+        // to signal; without this we would loop back and spawn a fresh,
+        // unsupervised child over the Stopped state.
+        {
+            let mut p = lockable.lock().unwrap();
+            if p.stopping.load(Ordering::SeqCst) {
+                info!("Stopped.");
+                p.state = State::Stopped;
+                break;
+            }
+        }
+
+        // Run the process
+        let child = {
+            let mut p = lockable.lock().unwrap();
+            let mut cmd = process::Command::new(&p.path);
+            cmd.args(&p.args[1..]);
+
+            // If a working directory is specified, set it.
+            if p.cwd.is_some() {
+                cmd.current_dir(p.cwd.as_ref().unwrap());
+            }
+
+            // Set up stdin, stdout, and stderr
+            cmd.stdin(process::Stdio::piped());
+            cmd.stdout(process::Stdio::piped());
+            cmd.stderr(process::Stdio::piped());
+
+            // Put the child in its own session/process group so the whole tree
+            // can be signalled as a unit later on. setsid makes the child a
+            // session and group leader, so its pgid equals its pid.
+            if p.kill_group {
+                unsafe {
+                    cmd.pre_exec(|| {
+                        // SAFETY: async-signal-safe, as required of a pre_exec hook.
+                        if libc::setsid() == -1 {
+                            return Err(std::io::Error::last_os_error());
+                        }
+                        Ok(())
+                    });
+                }
+            }
+
+            // Spawn the child
+            let child = Arc::new(Mutex::new(cmd.spawn().expect("Failed to run binary")));
+
+            // Hand this child's stdin to the long-lived writer pump, and spawn
+            // one reader thread per output stream. Each reader owns its
+            // BufReader and forwards complete lines as they arrive; when the
+            // child exits the pipe closes, the Lines iterator ends and the
+            // thread exits. This keeps the supervisor free to only wait for exit.
+            {
+                let stdin = child.lock().unwrap().stdin.take().unwrap();
+                *stdin_writer.lock().unwrap() = Some(BufWriter::new(stdin));
+            }
+            spawn_reader("overlord-stdout",
+                child.lock().unwrap().stdout.take().unwrap(),
+                p.stdout_sender.clone());
+            spawn_reader("overlord-stderr",
+                child.lock().unwrap().stderr.take().unwrap(),
+                p.stderr_sender.clone());
+
+            p.state = State::Running;
+            let pid = child.lock().unwrap().id();
+            p.pid = Some(pid);
+            // With setsid the child leads its own group, so the pgid is the pid.
+            p.pgid = if p.kill_group { Some(pid) } else { None };
+            p.child = Some(child.clone());
+            child
+        };
+
+        // Wait for the child to exit. How we wait is the runtime's business.
+        let exit_status = match rt.wait(&child) {
+            Ok(status) => {
+                let code = status.code();
+                if code.is_none() {
+                    error!("Killed by Signal");
+                }
+                lockable.lock().unwrap().exit_status = code;
+                Ok(code)
+            }
+            Err(e) => Err(e),
+        };
+        info!("exit code {:?}", exit_status);
+
+        // The child's stdin pipe is now dead; clear the writer slot so the pump
+        // doesn't write into it until the next child is spawned.
+        *stdin_writer.lock().unwrap() = None;
+
+        // Clear the child's identity now that it has exited, so a `stop` racing
+        // the restart-delay window does not signal a stale (possibly reused) pid.
+        // The pgid is kept aside for the group teardown before a restart.
+        let exited_pgid = {
+            let mut p = lockable.lock().unwrap();
+            p.pid = None;
+            p.child = None;
+            p.pgid.take()
+        };
+
+        // If `stop` asked us to shut down, the child has now exited during the
+        // grace window (or been killed). Land in Stopped and do not restart.
+        {
+            let mut p = lockable.lock().unwrap();
+            if p.stopping.load(Ordering::SeqCst) {
+                info!("Stopped.");
+                p.state = State::Stopped;
+                break;
+            }
+        }
+
+        // Depending on the exit status, restart or fail the process
+        match exit_status {
+            Ok(Some(0)) => {
+                info!("Exited with 0. Restarting...");
+            }
+            Ok(Some(_)) | Ok(None)  => {
+                info!("Failed. Restarting...");
+            }
+            Err(e) => {
+                error!("Error: {}. Not restarting...", e);
+                lockable.lock().unwrap().state = State::Failed;
+                break;
+            }
+        };
+
+        let restart_delay = {
+            let mut p = lockable.lock().unwrap();
+
+            // Do not restart more than p.max_restart_count times.
+            if p.restart_count >= p.max_restart_count {
+                error!("Restarted to often. Not restarting...");
+                p.state = State::Failed;
+                break;
+            }
+
+            // The child has exited, but any descendants it forked into its
+            // process group may still be alive. Tear the group down before
+            // respawning so they don't leak across restarts.
+            if let Some(pgid) = exited_pgid {
+                signal_group(pgid, libc::SIGKILL);
+            }
+
+            p.restart_count += 1;
+            p.state = State::Restarting;
+            p.restart_delay
+        };
+
+        rt.sleep(time::Duration::from_millis(restart_delay));
+    }
+}
+
+/// A supervision runtime backed by an async executor instead of a dedicated
+/// thread per process.
+///
+/// Rather than parking an OS thread on a 10ms `try_wait` poll, this drives each
+/// process as a task on a shared executor whose single background reaper thread
+/// `wait()`s on children and wakes the tasks — the model async-process
+/// documents. This lets a single Overlord supervise thousands of processes
+/// without thousands of polling threads.
+///
+/// Because the child is an `async_process::Child` rather than a std `Child`, it
+/// is not stored in `_Process::child` and `handle` is left unset (the task is
+/// detached on the shared executor). `Process::stop` therefore cannot
+/// bounded-wait or hard-kill it through the std path: it signals the pid with
+/// SIGTERM and the async supervisor observes `stopping` once the child exits.
+///
+/// This runtime does not apply `kill_group`: the child is not put in its own
+/// process group, no pgid is recorded, and only the direct child is signalled.
+#[cfg(feature = "async")]
+#[derive(Debug, Clone, Copy)]
+pub struct AsyncRuntime;
+
+#[cfg(feature = "async")]
+mod executor {
+    use std::sync::OnceLock;
+    use std::thread;
+
+    /// The process-wide executor whose single reaper thread drives every
+    /// supervised task.
+    static EXECUTOR: OnceLock<&'static async_executor::Executor<'static>> = OnceLock::new();
+
+    pub fn global() -> &'static async_executor::Executor<'static> {
+        EXECUTOR.get_or_init(|| {
+            let executor: &'static _ = Box::leak(Box::new(async_executor::Executor::new()));
+            thread::Builder::new()
+                .name("overlord-reaper".to_string())
+                .spawn(|| futures_lite::future::block_on(executor.run(futures_lite::future::pending::<()>())))
+                .expect("Failed to spawn reaper thread");
+            executor
+        })
+    }
+}
+
+#[cfg(feature = "async")]
+impl Runtime for AsyncRuntime {
+    fn spawn_supervised(&self, process: Process) {
+        executor::global().spawn(async move { supervise_async(process).await }).detach();
+    }
+
+    fn wait(&self, child: &Arc<Mutex<process::Child>>)
+        -> std::io::Result<process::ExitStatus> {
+        // A std `Child` can't be awaited directly, so this is a polling fallback
+        // for callers that drive a std child through the trait. The async
+        // supervisor (`supervise_async`) instead awaits `async_process::Child`'s
+        // `status()`, which is what actually shares the single reaper thread.
+        let child = child.clone();
+        futures_lite::future::block_on(async move {
+            loop {
+                if let Some(status) = child.lock().unwrap().try_wait()? {
+                    return Ok(status);
+                }
+                async_io::Timer::after(time::Duration::from_millis(10)).await;
+            }
+        })
+    }
+
+    fn sleep(&self, dur: time::Duration) {
+        futures_lite::future::block_on(async_io::Timer::after(dur));
+    }
+}
+
+/// Async counterpart of [`supervise`]: the same restart/state machine driven on
+/// the shared executor, awaiting the child through async-process so the reaper
+/// thread is shared across all supervised processes.
+#[cfg(feature = "async")]
+async fn supervise_async(lockable: Process) {
+    use futures_lite::{io::BufReader, AsyncBufReadExt, AsyncWriteExt, StreamExt};
+
+    // A single writer task drains the stdin channel into whichever child is
+    // currently running, swapped through this shared slot on each (re)spawn --
+    // the same model the native runtime uses, so stdin behaves identically.
+    let stdin_writer: Arc<Mutex<Option<async_process::ChildStdin>>> =
+        Arc::new(Mutex::new(None));
+    {
+        let stdin_receiver = lockable.lock().unwrap().stdin_receiver.take()
+            .expect("process supervised more than once");
+        let stdin_writer = stdin_writer.clone();
+        let stopping = lockable.lock().unwrap().stopping.clone();
+        executor::global().spawn(async move {
+            // Poll the std channel instead of blocking in `recv`, which would
+            // stall the shared executor thread and every task on it.
+            while !stopping.load(Ordering::SeqCst) {
+                let input = match stdin_receiver.try_recv() {
+                    Ok(input) => input,
+                    Err(mpsc::TryRecvError::Empty) => {
+                        async_io::Timer::after(time::Duration::from_millis(10)).await;
+                        continue;
+                    }
+                    Err(mpsc::TryRecvError::Disconnected) => break,
+                };
+                info!("received: {}", input);
+                let mut slot = stdin_writer.lock().unwrap().take();
+                if let Some(ref mut writer) = slot {
+                    if let Err(e) = writer.write_all(input.as_bytes()).await
+                        .and(writer.flush().await) {
+                        trace!("dropping stdin line; pipe closed: {}", e);
+                    }
+                }
+                *stdin_writer.lock().unwrap() = slot;
+            }
+        }).detach();
+    }
+
+    loop {
+        // Check the shutdown flag before (re)spawning; see `supervise`.
+        {
+            let mut p = lockable.lock().unwrap();
+            if p.stopping.load(Ordering::SeqCst) {
+                info!("Stopped.");
+                p.state = State::Stopped;
+                break;
+            }
+        }
+
+        let mut child = {
+            let p = lockable.lock().unwrap();
+            let mut cmd = async_process::Command::new(&p.path);
+            cmd.args(&p.args[1..]);
+            if let Some(ref cwd) = p.cwd {
+                cmd.current_dir(cwd);
+            }
+            cmd.stdin(async_process::Stdio::piped());
+            cmd.stdout(async_process::Stdio::piped());
+            cmd.stderr(async_process::Stdio::piped());
+            cmd.spawn().expect("Failed to run binary")
+        };
+
+        // Forward stdout/stderr lines as they arrive, each on its own task.
+        let stdout_sender = lockable.lock().unwrap().stdout_sender.clone();
+        let stderr_sender = lockable.lock().unwrap().stderr_sender.clone();
+        if let Some(stdout) = child.stdout.take() {
+            executor::global().spawn(async move {
+                let mut lines = BufReader::new(stdout).lines();
+                while let Some(Ok(line)) = lines.next().await {
+                    if stdout_sender.send(line).is_err() { break; }
+                }
+            }).detach();
+        }
+        if let Some(stderr) = child.stderr.take() {
+            executor::global().spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Some(Ok(line)) = lines.next().await {
+                    if stderr_sender.send(line).is_err() { break; }
+                }
+            }).detach();
+        }
+
+        if let Some(stdin) = child.stdin.take() {
+            *stdin_writer.lock().unwrap() = Some(stdin);
+        }
+
+        {
+            let mut p = lockable.lock().unwrap();
+            p.state = State::Running;
+            p.pid = Some(child.id());
+        }
+
+        let status = child.status().await;
+
+        // The child's stdin pipe is now dead; clear the writer slot and drop
+        // the stale pid so `stop` does not signal a reused one.
+        *stdin_writer.lock().unwrap() = None;
+        lockable.lock().unwrap().pid = None;
+        let exit_status = match status {
+            Ok(status) => {
+                let code = status.code();
+                if code.is_none() {
+                    error!("Killed by Signal");
+                }
+                lockable.lock().unwrap().exit_status = code;
+                Ok(code)
+            }
+            Err(e) => Err(e),
+        };
+        info!("exit code {:?}", exit_status);
+
+        {
+            let mut p = lockable.lock().unwrap();
+            if p.stopping.load(Ordering::SeqCst) {
+                info!("Stopped.");
+                p.state = State::Stopped;
+                break;
+            }
+        }
+
+        match exit_status {
+            Ok(Some(0)) => info!("Exited with 0. Restarting..."),
+            Ok(Some(_)) | Ok(None) => info!("Failed. Restarting..."),
+            Err(e) => {
+                error!("Error: {}. Not restarting...", e);
+                lockable.lock().unwrap().state = State::Failed;
+                break;
+            }
+        };
+
+        let restart_delay = {
+            let mut p = lockable.lock().unwrap();
+            if p.restart_count >= p.max_restart_count {
+                error!("Restarted to often. Not restarting...");
+                p.state = State::Failed;
+                break;
+            }
+            p.restart_count += 1;
+            p.state = State::Restarting;
+            p.restart_delay
+        };
+
+        async_io::Timer::after(time::Duration::from_millis(restart_delay)).await;
+    }
 }
 
 #[allow(unused_macros)]
 macro_rules! from_argv {
     ( $argv:expr ) => {{
         let _argv = $argv.iter().map(|s| s.to_string()).collect();
-        <Process as Runnable>::define_process($argv[0], $argv[0], _argv, None, None)
+        <Process as Runnable>::define_process($argv[0], $argv[0], _argv, None, None, false)
     }};
     ( $argv:expr, $cwd:expr ) => {{
         let _argv = $argv.iter().map(|s| s.to_string()).collect();
         <Process as Runnable>::define_process($argv[0], $argv[0], _argv,
                      None,
-                     Some($cwd.to_string()))
+                     Some($cwd.to_string()),
+                     false)
     }};
     ( $argv:expr, $cwd:expr, $restart_delay:expr ) => {{
         let _argv = $argv.iter().map(|s| s.to_string()).collect();
         <Process as Runnable>::define_process($argv[0], $argv[0], _argv,
                      Some($restart_delay),
-                     Some($cwd.to_string()))
+                     Some($cwd.to_string()),
+                     false)
+    }};
+    ( $argv:expr, $cwd:expr, $restart_delay:expr, $kill_group:expr ) => {{
+        let _argv = $argv.iter().map(|s| s.to_string()).collect();
+        <Process as Runnable>::define_process($argv[0], $argv[0], _argv,
+                     Some($restart_delay),
+                     Some($cwd.to_string()),
+                     $kill_group)
     }};
 }
 
 impl Runnable for Process {
     fn define_process(name: &str, path: &str, args: Vec<String>,
-           restart_delay: Option<u64>, cwd: Option<String>) -> Process {
+           restart_delay: Option<u64>, cwd: Option<String>, kill_group: bool) -> Process {
 
         // set up stdio channels
         let (stdin, stdin_receiver) = mpsc::channel();
@@ -93,159 +626,110 @@ impl Runnable for Process {
             max_restart_count: 5, // FIXME: this should be configurable
             pid: None,
 
+            kill_group: kill_group,
+            pgid: None,
+
+            stopping: Arc::new(AtomicBool::new(false)),
+
             stdin: stdin,
             stdout: stdout,
             stderr: stderr,
-            stdin_receiver: stdin_receiver,
+            stdin_receiver: Some(stdin_receiver),
             stdout_sender: stdout_sender,
             stderr_sender: stderr_sender,
 
-            stdin_writer: None,
-            stdout_reader: None,
-            stderr_reader: None,
-
             child: None,
         }))
     }
 
-    /// Launches the process.
+    /// Launches the process using the default [`NativeRuntime`].
     fn launch(self) {
-        let lockable = self.clone();
-        let handle = Some(thread::Builder::new()
-            .name("overlord".to_string())
-            .spawn(move || {
-                loop {
-                    // Run the process
-                    let child = {
-                        let mut p = lockable.lock().unwrap();
-                        let mut cmd = process::Command::new(&p.path);
-                        cmd.args(&p.args[1..]);
-
-                        // If a working directory is specified, set it.
-                        if p.cwd.is_some() {
-                            cmd.current_dir(p.cwd.as_ref().unwrap());
-                        }
-
-                        // Set up stdin, stdout, and stderr
-                        cmd.stdin(process::Stdio::piped());
-                        cmd.stdout(process::Stdio::piped());
-                        cmd.stderr(process::Stdio::piped());
-
-                        // Spawn the child
-                        let child = Arc::new(Mutex::new(cmd.spawn().expect("Failed to run binary")));
-
-                        // Set up BufReaders and BufWriters for stdin, stdout and stderr
-                        p.stdin_writer = Some(BufWriter::new(child.lock().unwrap().stdin.take().unwrap()));
-                        p.stdout_reader = Some(BufReader::new(child.lock().unwrap().stdout.take().unwrap()));
-                        p.stderr_reader = Some(BufReader::new(child.lock().unwrap().stderr.take().unwrap()));
-
-                        p.state = State::Running;
-                        p.pid = Some(child.lock().unwrap().id());
-                        p.child = Some(child.clone());
-                        child
-                    };
-
-                    // Process supervisor main loop
-                    let exit_status = loop {
-                        thread::sleep(time::Duration::from_millis(10));
-
-                        let mut p = lockable.lock().unwrap();
-                        // p is a MutexGuard<_Process>, so each access to fields of _Process calls
-                        // deref / deref_mut to get the underlying _Process. To avoid borrowing
-                        // conflicts, get a reference to the underlying _Process struct once.
-                        let p = &mut *p;
-
-                        // Handle stdio
-                        if let Some(ref mut stdout) = p.stdout_reader {
-                            for line in stdout.lines() {
-                                p.stdout_sender
-                                    .send(line.unwrap())
-                                    .expect("Could not send stdout");
-                            }
-                        }
-
-                        if let Some(ref mut stderr) = p.stderr_reader {
-                            for line in stderr.lines() {
-                                p.stderr_sender
-                                    .send(line.unwrap())
-                                    .expect("Could not send stderr");
-                            }
-                        }
-
-                        if let Some(ref mut stdin) = p.stdin_writer {
-                            if let Ok(input) = p.stdin_receiver.try_recv() {
-                                info!("received: {}", input);
-                                stdin.write_all(input.as_bytes())
-                                    .expect("Could not write to stdin");
-                            }
-                        }
-
-                        // Check if the process has already exited
-                        //
-                        // Note: we can't really use child.lock().unwrap().wait() here as that would
-                        //   - close stdin
-                        //   - require a mutable copy of child, and therefore make it necessary to
-                        //     lock its Mutex, rendering it impossible to call e.g. kill() on from
-                        //     another thread.
-                        //
-                        // The shared_child crate is also unsuitable as it has quite a few shortcomings
-                        // (e.g. still using a Mutex as opposed to a RwLock) and it's really not that
-                        // critical that we're fast here.
-                        match child.lock().unwrap().try_wait() {
-                            Ok(Some(status)) => {
-                                p.exit_status = if status.code().is_some() {
-                                    Some(status.code().expect("Could not get exit status"))
-                                } else {
-                                    error!("Killed by Signal");
-                                    None
-                                };
-                                break Ok(p.exit_status);
-                            }
-                            Err(e) => {
-                                break Err(e);
-                            }
-                            Ok(None) => {
-                                continue;
-                            }
-                        }
-                    };
-                    info!("exit code {:?}", exit_status);
-
-                    // Depending on the exit status, restart or fail the process
-                    match exit_status {
-                        Ok(Some(0)) => {
-                            info!("Exited with 0. Restarting...");
-                        }
-                        Ok(Some(_)) | Ok(None)  => {
-                            info!("Failed. Restarting...");
-                        }
-                        Err(e) => {
-                            error!("Error: {}. Not restarting...", e);
-                            let mut p = lockable.lock().unwrap();
-                            p.state = State::Failed;
-                            break;
-                        }
-                    };
-
-                    let restart_delay = {
-                        let mut p = lockable.lock().unwrap();
-
-                        // Do not restart more than p.max_restart_count times.
-                        if p.restart_count >= p.max_restart_count {
-                            error!("Restarted to often. Not restarting...");
-                            p.state = State::Failed;
-                            break;
-                        }
-
-                        p.restart_count += 1;
-                        p.state = State::Restarting;
-                        p.restart_delay
-                    };
+        NativeRuntime.spawn_supervised(self);
+    }
 
-                    thread::sleep(time::Duration::from_millis(restart_delay));
+    /// Gracefully stops the process.
+    ///
+    /// Flags the supervisor loop so it stops restarting the child, sends
+    /// SIGTERM, and waits up to `timeout` for the child to exit. If the child
+    /// is still alive once the deadline passes, it is hard-killed with SIGKILL.
+    /// A child that exits cleanly during the grace window lands in
+    /// `State::Stopped` rather than `State::Failed`.
+    fn stop(&self, timeout: time::Duration) {
+        // Tell the supervisor loop to stop restarting and grab the handles we
+        // need to signal and reap the child.
+        let (pid, pgid, child) = {
+            let mut p = self.lock().unwrap();
+            p.stopping.store(true, Ordering::SeqCst);
+            p.state = State::Stopping;
+            (p.pid, p.pgid, p.child.clone())
+        };
+
+        let child = match child {
+            Some(child) => child,
+            None => {
+                // No std `Child` to poll. Either nothing was launched yet, or
+                // the process is driven by `AsyncRuntime`, which stores no std
+                // child. If it is running, signal the pid and let its async
+                // supervisor observe `stopping` and settle the state; there is
+                // no std child here to bounded-wait on or hard-kill.
+                match (pid, pgid) {
+                    (_, Some(pgid)) => signal_group(pgid, libc::SIGTERM),
+                    (Some(pid), None) => signal(pid, libc::SIGTERM),
+                    (None, None) => { self.lock().unwrap().state = State::Stopped; }
                 }
-            }).expect("Failed to spawn process"));
-            self.lock().unwrap().handle = handle;
+                return;
+            }
+        };
+
+        // If the child has already exited (e.g. `stop` raced the restart-delay
+        // window), do not signal: its pid/pgid may have been reused by an
+        // unrelated process. The supervisor loop will settle the state.
+        if let Ok(Some(_)) = child.lock().unwrap().try_wait() {
+            let mut p = self.lock().unwrap();
+            if p.state == State::Stopping {
+                p.state = State::Stopped;
+            }
+            return;
+        }
+
+        // Politely ask the child to terminate. If it leads a process group,
+        // signal the whole group so its descendants go down with it.
+        match pgid {
+            Some(pgid) => signal_group(pgid, libc::SIGTERM),
+            None => if let Some(pid) = pid {
+                signal(pid, libc::SIGTERM);
+            },
+        }
+
+        // Poll try_wait until the child exits or the monotonic deadline passes.
+        let deadline = time::Instant::now() + timeout;
+        loop {
+            if let Ok(Some(_)) = child.lock().unwrap().try_wait() {
+                break;
+            }
+
+            if time::Instant::now() >= deadline {
+                // Grace window elapsed: escalate to an unconditional kill and
+                // reap the child so it does not linger as a zombie.
+                warn!("Stop timed out, sending SIGKILL");
+                if let Some(pgid) = pgid {
+                    signal_group(pgid, libc::SIGKILL);
+                }
+                let _ = child.lock().unwrap().kill();
+                let _ = child.lock().unwrap().wait();
+                break;
+            }
+
+            thread::sleep(time::Duration::from_millis(10));
+        }
+
+        // The supervisor loop observes `stopping` and moves us to Stopped once
+        // its own wait notices the exit, which can lag behind this return, so
+        // settle the state here.
+        let mut p = self.lock().unwrap();
+        if p.state == State::Stopping {
+            p.state = State::Stopped;
+        }
     }
 }
 
@@ -293,4 +777,23 @@ mod tests {
         sleep!(500);
         assert_eq!(p.lock().unwrap().stdout.recv(), Ok("test".to_string()));
     }
+
+    #[test]
+    fn test_stop_lands_in_stopped() {
+        // A long-lived child so we can stop it while it is still running.
+        let p = from_argv!(["sleep", "10"], "/", 100);
+        p.clone().launch();
+
+        sleep!(200);
+        assert_eq!(p.lock().unwrap().state, State::Running);
+
+        p.stop(time::Duration::from_millis(500));
+        assert_eq!(p.lock().unwrap().state, State::Stopped);
+
+        // A stopped process must not be restarted.
+        let restart_count = p.lock().unwrap().restart_count;
+        sleep!(300);
+        assert_eq!(p.lock().unwrap().state, State::Stopped);
+        assert_eq!(p.lock().unwrap().restart_count, restart_count);
+    }
 }
@@ -0,0 +1,280 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time;
+
+use process::{NativeRuntime, Process, Runnable, Runtime, State};
+
+/// Address of a peer node, e.g. `"10.0.0.2:7000"`.
+pub type Node = String;
+
+/// Serialise `state` for the status channel.
+fn state_name(state: &State) -> &'static str {
+    match *state {
+        State::Stopped => "stopped",
+        State::Starting => "starting",
+        State::Running => "running",
+        State::Restarting => "restarting",
+        State::Stopping => "stopping",
+        State::Failed => "failed",
+    }
+}
+
+/// Parse a state name received from a peer, ignoring anything unrecognised.
+fn parse_state(name: &str) -> Option<State> {
+    Some(match name {
+        "stopped" => State::Stopped,
+        "starting" => State::Starting,
+        "running" => State::Running,
+        "restarting" => State::Restarting,
+        "stopping" => State::Stopping,
+        "failed" => State::Failed,
+        _ => return None,
+    })
+}
+
+/// Write the process definition as a block of `key=value` header lines,
+/// terminated by a blank line. The wire protocol is line-oriented throughout,
+/// matching overlord's line-based stdio channels.
+fn write_definition(stream: &mut TcpStream, process: &Process) -> io::Result<()> {
+    let p = process.lock().unwrap();
+    writeln!(stream, "name={}", p.name)?;
+    writeln!(stream, "path={}", p.path)?;
+    for arg in &p.args {
+        writeln!(stream, "arg={}", arg)?;
+    }
+    if let Some(ref cwd) = p.cwd {
+        writeln!(stream, "cwd={}", cwd)?;
+    }
+    writeln!(stream, "restart_delay={}", p.restart_delay)?;
+    writeln!(stream, "kill_group={}", p.kill_group)?;
+    writeln!(stream, "max_restart_count={}", p.max_restart_count)?;
+    writeln!(stream)?;
+    stream.flush()
+}
+
+/// Read a process definition written by `write_definition` and rebuild a local
+/// [`Process`] from it.
+fn read_definition<R: BufRead>(reader: &mut R) -> io::Result<Process> {
+    let mut name = String::new();
+    let mut path = String::new();
+    let mut args: Vec<String> = Vec::new();
+    let mut cwd: Option<String> = None;
+    let mut restart_delay: Option<u64> = None;
+    let mut kill_group = false;
+    let mut max_restart_count: Option<u64> = None;
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let line = line.trim_end_matches('\n');
+        if line.is_empty() {
+            break;
+        }
+        let mut parts = line.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some("name"), Some(v)) => name = v.to_string(),
+            (Some("path"), Some(v)) => path = v.to_string(),
+            (Some("arg"), Some(v)) => args.push(v.to_string()),
+            (Some("cwd"), Some(v)) => cwd = Some(v.to_string()),
+            (Some("restart_delay"), Some(v)) => restart_delay = v.parse().ok(),
+            (Some("kill_group"), Some(v)) => kill_group = v == "true",
+            (Some("max_restart_count"), Some(v)) => max_restart_count = v.parse().ok(),
+            _ => trace!("ignoring unknown definition line: {}", line),
+        }
+    }
+
+    let process = <Process as Runnable>::define_process(
+        &name, &path, args, restart_delay, cwd, kill_group);
+    if let Some(max) = max_restart_count {
+        process.lock().unwrap().max_restart_count = max;
+    }
+    Ok(process)
+}
+
+/// Spawn a thread forwarding every line from `receiver` onto `stream`, each
+/// prefixed with `tag`.
+fn forward_lines(name: &str, tag: char, stream: TcpStream,
+                 receiver: std::sync::mpsc::Receiver<String>) {
+    let mut stream = stream;
+    thread::Builder::new()
+        .name(name.to_string())
+        .spawn(move || {
+            for line in receiver.iter() {
+                if writeln!(stream, "{} {}", tag, line).is_err() {
+                    break;
+                }
+                let _ = stream.flush();
+            }
+        }).expect("Failed to spawn tunnel forwarder");
+}
+
+/// The peer end of a remote supervision link.
+///
+/// Listens for originating Overlords, rebuilds each shipped process definition
+/// locally, supervises it with the [`NativeRuntime`], and tunnels its stdio and
+/// state back over the same connection.
+pub fn serve(addr: &str) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    info!("remote agent listening on {}", addr);
+    for stream in listener.incoming() {
+        let stream = stream?;
+        thread::Builder::new()
+            .name("overlord-peer".to_string())
+            .spawn(move || {
+                if let Err(e) = handle_peer(stream) {
+                    error!("peer connection failed: {}", e);
+                }
+            }).expect("Failed to spawn peer handler");
+    }
+    Ok(())
+}
+
+/// Grace period used to tear down a remote process when its originator
+/// disconnects without an explicit stop.
+const REMOTE_TEARDOWN_TIMEOUT: time::Duration = time::Duration::from_secs(5);
+
+fn handle_peer(stream: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let process = read_definition(&mut reader)?;
+    info!("remote spawn {:?}", process);
+
+    // Supervise the process locally, then tunnel its stdio/state home. Mark it
+    // Starting first so the state forwarder does not take the initial Stopped
+    // for a terminal state before the supervisor has spawned the child.
+    process.lock().unwrap().state = State::Starting;
+    NativeRuntime.spawn_supervised(process.clone());
+    tunnel_out(&process, stream.try_clone()?);
+
+    // Inbound frames from the originator: `I <stdin>` feeds stdin, `STOP <ms>`
+    // (or the connection dropping) tears the process down so it is never
+    // orphaned.
+    let stdin = process.lock().unwrap().stdin.clone();
+    for line in reader.lines() {
+        let line = line?;
+        if let Some(rest) = line.strip_prefix("I ") {
+            if stdin.send(rest.to_string()).is_err() {
+                break;
+            }
+        } else if let Some(rest) = line.strip_prefix("STOP ") {
+            let timeout = rest.parse().map(time::Duration::from_millis)
+                .unwrap_or(REMOTE_TEARDOWN_TIMEOUT);
+            process.stop(timeout);
+            return Ok(());
+        }
+    }
+
+    // The originator disconnected; don't leave the supervised process running.
+    info!("originator disconnected; stopping remote process");
+    process.stop(REMOTE_TEARDOWN_TIMEOUT);
+    Ok(())
+}
+
+/// Forward a locally-supervised process's stdout/stderr and state back to the
+/// originator over `stream`.
+fn tunnel_out(process: &Process, stream: TcpStream) {
+    let (stdout, stderr) = {
+        // The supervised process writes its output into the _Process stdout/
+        // stderr receivers. Take them over so the tunnel is the sole consumer,
+        // swapping in fresh (dangling) channels to keep the struct well-formed.
+        let mut p = process.lock().unwrap();
+        let (_, stdout_rx) = std::sync::mpsc::channel::<String>();
+        let (_, stderr_rx) = std::sync::mpsc::channel::<String>();
+        (std::mem::replace(&mut p.stdout, stdout_rx),
+         std::mem::replace(&mut p.stderr, stderr_rx))
+    };
+
+    forward_lines("overlord-tunnel-out", 'O', stream.try_clone()
+        .expect("clone stream"), stdout);
+    forward_lines("overlord-tunnel-err", 'E', stream.try_clone()
+        .expect("clone stream"), stderr);
+
+    // Poll state/exit_status/restart_count and push changes as they happen.
+    let process = process.clone();
+    thread::Builder::new()
+        .name("overlord-tunnel-state".to_string())
+        .spawn(move || {
+            let mut stream = stream;
+            let mut last_state = String::new();
+            let mut last_exit: Option<i32> = None;
+            let mut last_restart = u64::max_value();
+            loop {
+                let (state, terminal, exit, restart) = {
+                    let p = process.lock().unwrap();
+                    let terminal = p.state == State::Stopped || p.state == State::Failed;
+                    (state_name(&p.state).to_string(), terminal, p.exit_status, p.restart_count)
+                };
+                if state != last_state {
+                    if writeln!(stream, "S {}", state).is_err() { break; }
+                    last_state = state;
+                }
+                if exit != last_exit {
+                    let code = exit.map(|c| c.to_string()).unwrap_or_else(|| "-".to_string());
+                    if writeln!(stream, "X {}", code).is_err() { break; }
+                    last_exit = exit;
+                }
+                if restart != last_restart {
+                    if writeln!(stream, "R {}", restart).is_err() { break; }
+                    last_restart = restart;
+                }
+                let _ = stream.flush();
+                // Once the process has reached a terminal state there is nothing
+                // left to report; stop polling rather than leaking this thread.
+                if terminal {
+                    break;
+                }
+                thread::sleep(time::Duration::from_millis(50));
+            }
+        }).expect("Failed to spawn state forwarder");
+}
+
+/// Launch `process` on `node` and tunnel its stdio channels over the wire, so
+/// callers use the same `Sender`/`Receiver` API regardless of where the process
+/// runs. State, exit status and restart count reported by the peer are applied
+/// back onto the local [`Process`].
+pub fn spawn_remote(node: &Node, process: Process) -> io::Result<()> {
+    let stream = TcpStream::connect(node)?;
+    let mut writer = stream.try_clone()?;
+    write_definition(&mut writer, &process)?;
+
+    // Forward local stdin onto the link as `I <line>`.
+    let stdin_receiver = process.lock().unwrap().stdin_receiver.take()
+        .expect("process launched more than once");
+    forward_lines("overlord-remote-in", 'I', stream.try_clone()?, stdin_receiver);
+
+    // Read stdio and status frames back from the peer.
+    let process = process.clone();
+    thread::Builder::new()
+        .name("overlord-remote".to_string())
+        .spawn(move || {
+            let reader = BufReader::new(stream);
+            for line in reader.lines() {
+                let line = match line { Ok(l) => l, Err(_) => break };
+                // Split off exactly the one delimiter space so tunneled output
+                // keeps its own leading whitespace intact.
+                let (tag, rest) = match line.find(' ') {
+                    Some(i) => (&line[..i], &line[i + 1..]),
+                    None => (line.as_str(), ""),
+                };
+                match tag {
+                    "O" => { let _ = process.lock().unwrap().stdout_sender.send(rest.to_string()); }
+                    "E" => { let _ = process.lock().unwrap().stderr_sender.send(rest.to_string()); }
+                    "S" => if let Some(state) = parse_state(rest) {
+                        process.lock().unwrap().state = state;
+                    },
+                    "X" => {
+                        let code = if rest == "-" { None } else { rest.parse().ok() };
+                        process.lock().unwrap().exit_status = code;
+                    }
+                    "R" => if let Ok(count) = rest.parse() {
+                        process.lock().unwrap().restart_count = count;
+                    },
+                    _ => trace!("ignoring unknown frame: {}", line),
+                }
+            }
+        }).expect("Failed to spawn remote reader");
+
+    Ok(())
+}